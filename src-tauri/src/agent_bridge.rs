@@ -1,39 +1,153 @@
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, oneshot, watch, Mutex};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue};
 use tokio_tungstenite::{connect_async, tungstenite::Message};
+use uuid::Uuid;
 
-use crate::{Agent, AgentConfig};
+use crate::{Agent, AgentConfig, RuntimeConnectionConfig};
+
+/// How long `send_and_receive` waits for a correlated reply before giving up and
+/// dropping the pending entry, unless overridden by
+/// `RuntimeConnectionConfig::request_timeout_ms`.
+pub(crate) const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often the heartbeat task pings the runtime.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long we tolerate silence from the runtime before declaring the connection dead.
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(45);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum RuntimeMessage {
     #[serde(rename = "start_agent")]
-    StartAgent { config: AgentConfig },
+    StartAgent {
+        request_id: String,
+        config: AgentConfig,
+    },
     #[serde(rename = "stop_agent")]
-    StopAgent { agent_id: String },
+    StopAgent {
+        request_id: String,
+        agent_id: String,
+    },
     #[serde(rename = "list_agents")]
-    ListAgents,
+    ListAgents { request_id: String },
     #[serde(rename = "get_agent")]
-    GetAgent { agent_id: String },
+    GetAgent {
+        request_id: String,
+        agent_id: String,
+    },
     #[serde(rename = "send_message")]
-    SendMessage { agent_id: String, message: String },
+    SendMessage {
+        request_id: String,
+        agent_id: String,
+        message: String,
+    },
+}
+
+impl RuntimeMessage {
+    fn request_id(&self) -> &str {
+        match self {
+            RuntimeMessage::StartAgent { request_id, .. }
+            | RuntimeMessage::StopAgent { request_id, .. }
+            | RuntimeMessage::ListAgents { request_id }
+            | RuntimeMessage::GetAgent { request_id, .. }
+            | RuntimeMessage::SendMessage { request_id, .. } => request_id,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum RuntimeResponse {
     #[serde(rename = "agent")]
-    Agent { agent: Agent },
+    Agent { request_id: String, agent: Agent },
     #[serde(rename = "agents")]
-    Agents { agents: Vec<Agent> },
+    Agents {
+        request_id: String,
+        agents: Vec<Agent>,
+    },
     #[serde(rename = "agent_optional")]
-    AgentOptional { agent: Option<Agent> },
+    AgentOptional {
+        request_id: String,
+        agent: Option<Agent>,
+    },
     #[serde(rename = "success")]
-    Success,
+    Success { request_id: String },
     #[serde(rename = "error")]
-    Error { message: String },
+    Error {
+        request_id: String,
+        message: String,
+    },
+    /// Unsolicited chunk of an agent's incremental output (tokens, logs, tool calls).
+    /// Not a reply to any request, so it carries no `request_id`.
+    #[serde(rename = "agent_output")]
+    AgentOutput { agent_id: String, chunk: String },
+    /// Unsolicited notification that an agent's status changed.
+    #[serde(rename = "agent_status_changed")]
+    AgentStatusChanged { agent_id: String, status: String },
+}
+
+impl RuntimeResponse {
+    /// Unsolicited frames are pushed by the runtime on its own schedule and are not
+    /// replies to a pending request, so they are routed to the stream broadcast
+    /// instead of being matched up with a `send_and_receive` caller.
+    fn request_id(&self) -> Option<&str> {
+        match self {
+            RuntimeResponse::Agent { request_id, .. }
+            | RuntimeResponse::Agents { request_id, .. }
+            | RuntimeResponse::AgentOptional { request_id, .. }
+            | RuntimeResponse::Success { request_id }
+            | RuntimeResponse::Error { request_id, .. } => Some(request_id),
+            RuntimeResponse::AgentOutput { .. } | RuntimeResponse::AgentStatusChanged { .. } => {
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod correlation_tests {
+    use super::*;
+
+    #[test]
+    fn runtime_message_request_id_matches_the_field_it_was_constructed_with() {
+        let msg = RuntimeMessage::GetAgent {
+            request_id: "req-1".to_string(),
+            agent_id: "agent-1".to_string(),
+        };
+
+        assert_eq!(msg.request_id(), "req-1");
+    }
+
+    #[test]
+    fn runtime_response_request_id_is_some_for_correlated_replies() {
+        let response = RuntimeResponse::Success {
+            request_id: "req-1".to_string(),
+        };
+
+        assert_eq!(response.request_id(), Some("req-1"));
+    }
+
+    #[test]
+    fn runtime_response_request_id_is_none_for_unsolicited_frames() {
+        let output = RuntimeResponse::AgentOutput {
+            agent_id: "agent-1".to_string(),
+            chunk: "hello".to_string(),
+        };
+        let status = RuntimeResponse::AgentStatusChanged {
+            agent_id: "agent-1".to_string(),
+            status: "running".to_string(),
+        };
+
+        assert_eq!(output.request_id(), None);
+        assert_eq!(status.request_id(), None);
+    }
 }
 
 type WsSink = futures_util::stream::SplitSink<
@@ -49,60 +163,165 @@ type WsStream = futures_util::stream::SplitStream<
     >,
 >;
 
+/// Capacity of the broadcast channel that fans out unsolicited runtime frames
+/// (agent output, status changes) to any number of subscribers.
+const STREAM_CHANNEL_CAPACITY: usize = 1024;
+
+type PendingRequests = Arc<Mutex<HashMap<String, oneshot::Sender<RuntimeResponse>>>>;
+
+/// Broadcasts "the connection died" from whichever of the reader/heartbeat tasks
+/// notices first. Backed by a `watch` rather than a `Notify`: a `Notify::notify_waiters`
+/// call is lost if it fires before a waiter subscribes, but a `watch` replays its last
+/// value to every new subscriber, so `wait()` can't miss a death signaled moments ago.
+#[derive(Clone)]
+struct DeathSignal(Arc<watch::Sender<bool>>);
+
+impl DeathSignal {
+    fn new() -> Self {
+        Self(Arc::new(watch::channel(false).0))
+    }
+
+    fn mark_dead(&self) {
+        // `send` refuses to store the value while nobody is subscribed; `send_replace`
+        // always stores it, so a later `wait()` still sees the death.
+        self.0.send_replace(true);
+    }
+
+    async fn wait(&self) {
+        let mut rx = self.0.subscribe();
+        if *rx.borrow() {
+            return;
+        }
+        let _ = rx.changed().await;
+    }
+
+    #[cfg(test)]
+    fn is_dead(&self) -> bool {
+        *self.0.subscribe().borrow()
+    }
+}
+
 pub struct AgentBridge {
     write: Arc<Mutex<WsSink>>,
-    read: Arc<Mutex<WsStream>>,
+    /// Requests awaiting a correlated reply, keyed by the `request_id` that was sent.
+    /// The reader task fulfills and removes the matching entry as replies arrive.
+    pending_requests: PendingRequests,
+    /// Unsolicited frames (agent output, status changes) are published here; Tauri
+    /// commands subscribe to forward them to the webview.
+    stream_tx: broadcast::Sender<RuntimeResponse>,
+    /// Signaled once, by either the reader or the heartbeat task, the moment the
+    /// connection is judged dead. Callers await this to learn when to reconnect.
+    dead: DeathSignal,
+    /// Tasks owning the read half and the ping loop. Aborted by `shutdown` so a
+    /// disconnect doesn't leave either running against a socket nobody reads anymore.
+    reader_handle: tokio::task::JoinHandle<()>,
+    heartbeat_handle: tokio::task::JoinHandle<()>,
+    /// How long `send_and_receive` waits for a correlated reply; set from
+    /// `RuntimeConnectionConfig::resolved_request_timeout` at connect time.
+    request_timeout: Duration,
 }
 
 impl AgentBridge {
-    pub async fn connect(url: &str) -> Result<Self, String> {
-        let (ws_stream, _) = connect_async(url)
-            .await
-            .map_err(|e| format!("Failed to connect: {}", e))?;
+    pub async fn connect(config: &RuntimeConnectionConfig) -> Result<Self, String> {
+        let request = build_handshake_request(config)?;
+
+        let (ws_stream, _) =
+            tokio::time::timeout(config.resolved_connect_timeout(), connect_async(request))
+                .await
+                .map_err(|_| "Timed out connecting to runtime".to_string())?
+                .map_err(|e| format!("Failed to connect: {}", e))?;
 
         let (write, read) = ws_stream.split();
+        let write = Arc::new(Mutex::new(write));
+        let (stream_tx, _) = broadcast::channel(STREAM_CHANNEL_CAPACITY);
+        let pending_requests = Arc::new(Mutex::new(HashMap::new()));
+        let last_pong = Arc::new(Mutex::new(Instant::now()));
+        let dead = DeathSignal::new();
+
+        let reader_handle = spawn_reader(
+            read,
+            pending_requests.clone(),
+            stream_tx.clone(),
+            last_pong.clone(),
+            dead.clone(),
+        );
+        let heartbeat_handle = spawn_heartbeat(write.clone(), last_pong, dead.clone());
 
         Ok(Self {
-            write: Arc::new(Mutex::new(write)),
-            read: Arc::new(Mutex::new(read)),
+            write,
+            pending_requests,
+            stream_tx,
+            dead,
+            reader_handle,
+            heartbeat_handle,
+            request_timeout: config.resolved_request_timeout(),
         })
     }
 
+    /// Subscribe to unsolicited agent output and status frames.
+    pub fn subscribe(&self) -> broadcast::Receiver<RuntimeResponse> {
+        self.stream_tx.subscribe()
+    }
+
+    /// Resolves once the connection has been judged dead (missed heartbeats, a read
+    /// error, or the socket closing). Callers use this to trigger a reconnect. Safe to
+    /// call after the connection is already dead, since `DeathSignal` replays its last
+    /// value to new waiters instead of only notifying ones that were already waiting.
+    pub async fn wait_until_dead(&self) {
+        self.dead.wait().await
+    }
+
+    /// Tears the connection down: stops the reader and heartbeat tasks and sends a
+    /// WebSocket close frame. Call this on an explicit disconnect (or before replacing
+    /// a pooled bridge) so the socket and its background tasks don't leak.
+    pub async fn shutdown(&self) {
+        self.reader_handle.abort();
+        self.heartbeat_handle.abort();
+
+        let mut write = self.write.lock().await;
+        let _ = write.send(Message::Close(None)).await;
+        let _ = write.close().await;
+    }
+
     async fn send_and_receive(&self, msg: RuntimeMessage) -> Result<RuntimeResponse, String> {
+        let request_id = msg.request_id().to_string();
         let json = serde_json::to_string(&msg).map_err(|e| e.to_string())?;
 
+        let (tx, rx) = oneshot::channel();
+        self.pending_requests
+            .lock()
+            .await
+            .insert(request_id.clone(), tx);
+
         {
             let mut write = self.write.lock().await;
-            write
-                .send(Message::Text(json))
-                .await
-                .map_err(|e| e.to_string())?;
+            if let Err(e) = write.send(Message::Text(json)).await {
+                self.pending_requests.lock().await.remove(&request_id);
+                return Err(e.to_string());
+            }
         }
 
-        {
-            let mut read = self.read.lock().await;
-            if let Some(result) = read.next().await {
-                match result {
-                    Ok(Message::Text(text)) => {
-                        serde_json::from_str(&text).map_err(|e| e.to_string())
-                    }
-                    Ok(_) => Err("Unexpected message type".to_string()),
-                    Err(e) => Err(e.to_string()),
-                }
-            } else {
-                Err("Connection closed".to_string())
+        match tokio::time::timeout(self.request_timeout, rx).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err("Connection closed".to_string()),
+            Err(_) => {
+                self.pending_requests.lock().await.remove(&request_id);
+                Err("Timed out waiting for runtime response".to_string())
             }
         }
     }
 
     pub async fn start_agent(&self, config: AgentConfig) -> Result<Agent, String> {
         let response = self
-            .send_and_receive(RuntimeMessage::StartAgent { config })
+            .send_and_receive(RuntimeMessage::StartAgent {
+                request_id: Uuid::new_v4().to_string(),
+                config,
+            })
             .await?;
 
         match response {
-            RuntimeResponse::Agent { agent } => Ok(agent),
-            RuntimeResponse::Error { message } => Err(message),
+            RuntimeResponse::Agent { agent, .. } => Ok(agent),
+            RuntimeResponse::Error { message, .. } => Err(message),
             _ => Err("Unexpected response".to_string()),
         }
     }
@@ -110,23 +329,28 @@ impl AgentBridge {
     pub async fn stop_agent(&self, agent_id: &str) -> Result<(), String> {
         let response = self
             .send_and_receive(RuntimeMessage::StopAgent {
+                request_id: Uuid::new_v4().to_string(),
                 agent_id: agent_id.to_string(),
             })
             .await?;
 
         match response {
-            RuntimeResponse::Success => Ok(()),
-            RuntimeResponse::Error { message } => Err(message),
+            RuntimeResponse::Success { .. } => Ok(()),
+            RuntimeResponse::Error { message, .. } => Err(message),
             _ => Err("Unexpected response".to_string()),
         }
     }
 
     pub async fn list_agents(&self) -> Result<Vec<Agent>, String> {
-        let response = self.send_and_receive(RuntimeMessage::ListAgents).await?;
+        let response = self
+            .send_and_receive(RuntimeMessage::ListAgents {
+                request_id: Uuid::new_v4().to_string(),
+            })
+            .await?;
 
         match response {
-            RuntimeResponse::Agents { agents } => Ok(agents),
-            RuntimeResponse::Error { message } => Err(message),
+            RuntimeResponse::Agents { agents, .. } => Ok(agents),
+            RuntimeResponse::Error { message, .. } => Err(message),
             _ => Err("Unexpected response".to_string()),
         }
     }
@@ -134,13 +358,14 @@ impl AgentBridge {
     pub async fn get_agent(&self, agent_id: &str) -> Result<Option<Agent>, String> {
         let response = self
             .send_and_receive(RuntimeMessage::GetAgent {
+                request_id: Uuid::new_v4().to_string(),
                 agent_id: agent_id.to_string(),
             })
             .await?;
 
         match response {
-            RuntimeResponse::AgentOptional { agent } => Ok(agent),
-            RuntimeResponse::Error { message } => Err(message),
+            RuntimeResponse::AgentOptional { agent, .. } => Ok(agent),
+            RuntimeResponse::Error { message, .. } => Err(message),
             _ => Err("Unexpected response".to_string()),
         }
     }
@@ -148,15 +373,223 @@ impl AgentBridge {
     pub async fn send_message(&self, agent_id: &str, message: &str) -> Result<(), String> {
         let response = self
             .send_and_receive(RuntimeMessage::SendMessage {
+                request_id: Uuid::new_v4().to_string(),
                 agent_id: agent_id.to_string(),
                 message: message.to_string(),
             })
             .await?;
 
         match response {
-            RuntimeResponse::Success => Ok(()),
-            RuntimeResponse::Error { message } => Err(message),
+            RuntimeResponse::Success { .. } => Ok(()),
+            RuntimeResponse::Error { message, .. } => Err(message),
             _ => Err("Unexpected response".to_string()),
         }
     }
 }
+
+/// Builds the WebSocket handshake request for `config`'s resolved URL, attaching an
+/// `Authorization: Bearer <token>` header when an auth token is configured and any
+/// extra handshake headers on top. `wss://` URLs are upgraded to TLS automatically by
+/// `connect_async` via `MaybeTlsStream`.
+fn build_handshake_request(
+    config: &RuntimeConnectionConfig,
+) -> Result<tokio_tungstenite::tungstenite::http::Request<()>, String> {
+    let mut request = config
+        .resolved_url()
+        .into_client_request()
+        .map_err(|e| format!("Invalid runtime URL: {}", e))?;
+
+    let headers = request.headers_mut();
+
+    if let Some(token) = config.resolved_auth_token() {
+        let value = HeaderValue::from_str(&format!("Bearer {}", token))
+            .map_err(|e| format!("Invalid auth token: {}", e))?;
+        headers.insert(tokio_tungstenite::tungstenite::http::header::AUTHORIZATION, value);
+    }
+
+    for (name, value) in &config.handshake_headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| format!("Invalid handshake header name {}: {}", name, e))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|e| format!("Invalid handshake header value: {}", e))?;
+        headers.insert(name, value);
+    }
+
+    Ok(request)
+}
+
+#[cfg(test)]
+mod handshake_tests {
+    use super::*;
+
+    #[test]
+    fn uses_resolved_url_with_no_extra_headers_by_default() {
+        let config = RuntimeConnectionConfig {
+            url: Some("ws://example.test:9847".to_string()),
+            ..Default::default()
+        };
+
+        let request = build_handshake_request(&config).unwrap();
+
+        assert_eq!(request.uri(), "ws://example.test:9847/");
+        assert!(request.headers().get("authorization").is_none());
+    }
+
+    #[test]
+    fn sets_bearer_authorization_header_when_auth_token_is_configured() {
+        let config = RuntimeConnectionConfig {
+            url: Some("ws://example.test:9847".to_string()),
+            auth_token: Some("secret-token".to_string()),
+            ..Default::default()
+        };
+
+        let request = build_handshake_request(&config).unwrap();
+
+        assert_eq!(
+            request.headers().get("authorization").unwrap(),
+            "Bearer secret-token"
+        );
+    }
+
+    #[test]
+    fn applies_custom_handshake_headers() {
+        let mut handshake_headers = HashMap::new();
+        handshake_headers.insert("x-tenant".to_string(), "acme".to_string());
+
+        let config = RuntimeConnectionConfig {
+            url: Some("ws://example.test:9847".to_string()),
+            handshake_headers,
+            ..Default::default()
+        };
+
+        let request = build_handshake_request(&config).unwrap();
+
+        assert_eq!(request.headers().get("x-tenant").unwrap(), "acme");
+    }
+
+    #[test]
+    fn rejects_an_invalid_url() {
+        let config = RuntimeConnectionConfig {
+            url: Some("not a url".to_string()),
+            ..Default::default()
+        };
+
+        assert!(build_handshake_request(&config).is_err());
+    }
+}
+
+/// Owns the read half of the socket for the lifetime of the connection and classifies
+/// every inbound frame: correlated replies are matched to their `request_id` and handed
+/// to the waiting `send_and_receive` caller, unsolicited frames are forwarded to
+/// `stream_tx`. Any traffic resets the heartbeat's `last_pong` clock, and the socket
+/// closing or erroring marks the connection dead.
+fn spawn_reader(
+    mut read: WsStream,
+    pending_requests: PendingRequests,
+    stream_tx: broadcast::Sender<RuntimeResponse>,
+    last_pong: Arc<Mutex<Instant>>,
+    dead: DeathSignal,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(result) = read.next().await {
+            let text = match result {
+                Ok(message) => {
+                    *last_pong.lock().await = Instant::now();
+                    match message {
+                        Message::Text(text) => text,
+                        _ => continue,
+                    }
+                }
+                Err(_) => break,
+            };
+
+            let response: RuntimeResponse = match serde_json::from_str(&text) {
+                Ok(response) => response,
+                Err(_) => continue,
+            };
+
+            let Some(request_id) = response.request_id() else {
+                let _ = stream_tx.send(response);
+                continue;
+            };
+
+            if let Some(tx) = pending_requests.lock().await.remove(request_id) {
+                let _ = tx.send(response);
+            }
+        }
+
+        dead.mark_dead();
+    })
+}
+
+/// Periodically pings the runtime and watches `last_pong`; if a ping can't be sent or
+/// no pong/traffic has arrived within `HEARTBEAT_TIMEOUT`, the connection is marked dead.
+fn spawn_heartbeat(
+    write: Arc<Mutex<WsSink>>,
+    last_pong: Arc<Mutex<Instant>>,
+    dead: DeathSignal,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        interval.tick().await; // first tick fires immediately; skip it
+
+        loop {
+            interval.tick().await;
+
+            let ping_sent = write
+                .lock()
+                .await
+                .send(Message::Ping(Vec::new()))
+                .await
+                .is_ok();
+
+            if !ping_sent || heartbeat_expired(*last_pong.lock().await) {
+                dead.mark_dead();
+                break;
+            }
+        }
+    })
+}
+
+/// Whether `last_pong` is stale enough that the connection should be declared dead.
+fn heartbeat_expired(last_pong: Instant) -> bool {
+    last_pong.elapsed() > HEARTBEAT_TIMEOUT
+}
+
+#[cfg(test)]
+mod heartbeat_tests {
+    use super::*;
+
+    #[test]
+    fn heartbeat_is_not_expired_right_after_a_pong() {
+        assert!(!heartbeat_expired(Instant::now()));
+    }
+
+    #[test]
+    fn heartbeat_is_expired_once_the_timeout_has_elapsed() {
+        let last_pong = Instant::now() - HEARTBEAT_TIMEOUT - Duration::from_secs(1);
+        assert!(heartbeat_expired(last_pong));
+    }
+}
+
+#[cfg(test)]
+mod death_signal_tests {
+    use super::*;
+
+    #[test]
+    fn is_not_dead_until_marked() {
+        let dead = DeathSignal::new();
+        assert!(!dead.is_dead());
+    }
+
+    #[test]
+    fn a_subscriber_that_starts_waiting_after_death_was_signaled_still_observes_it() {
+        let dead = DeathSignal::new();
+        dead.mark_dead();
+
+        // A bare `Notify::notify_waiters()` would lose this: the signal fired before
+        // anyone subscribed, and a `Notify` waiter that wasn't already parked never
+        // finds out. `DeathSignal` must not have that gap.
+        assert!(dead.is_dead());
+    }
+}
@@ -1,11 +1,64 @@
+use futures_util::future::join_all;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tauri::State;
-use tokio::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::{broadcast, Mutex};
+use tokio::task::JoinHandle;
 
 mod agent_bridge;
 
-use agent_bridge::AgentBridge;
+use agent_bridge::{AgentBridge, RuntimeResponse};
+
+const DEFAULT_RUNTIME_URL: &str = "ws://127.0.0.1:9847";
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const INITIAL_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Where and how to reach an agent runtime, letting the desktop app point at a remote
+/// or secured runtime instead of only a local dev server. An unset `url` falls back to
+/// `ORKIS_RUNTIME_URL`, then to a hardcoded default; an unset `auth_token` falls back to
+/// `ORKIS_RUNTIME_TOKEN`, else no token is sent. Unset timeouts use hardcoded defaults
+/// and read no environment variable.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuntimeConnectionConfig {
+    pub url: Option<String>,
+    pub auth_token: Option<String>,
+    pub connect_timeout_ms: Option<u64>,
+    /// Overrides how long `AgentBridge::send_and_receive` waits for a correlated
+    /// reply; falls back to `agent_bridge::DEFAULT_REQUEST_TIMEOUT` when unset.
+    pub request_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub handshake_headers: HashMap<String, String>,
+}
+
+impl RuntimeConnectionConfig {
+    fn resolved_url(&self) -> String {
+        self.url
+            .clone()
+            .or_else(|| std::env::var("ORKIS_RUNTIME_URL").ok())
+            .unwrap_or_else(|| DEFAULT_RUNTIME_URL.to_string())
+    }
+
+    fn resolved_auth_token(&self) -> Option<String> {
+        self.auth_token
+            .clone()
+            .or_else(|| std::env::var("ORKIS_RUNTIME_TOKEN").ok())
+    }
+
+    fn resolved_connect_timeout(&self) -> Duration {
+        self.connect_timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_CONNECT_TIMEOUT)
+    }
+
+    fn resolved_request_timeout(&self) -> Duration {
+        self.request_timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(agent_bridge::DEFAULT_REQUEST_TIMEOUT)
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentConfig {
@@ -26,97 +79,320 @@ pub struct Agent {
     pub started_at: String,
 }
 
+/// An `Agent` tagged with the name of the runtime it was returned by, as produced by
+/// `list_all_agents` fanning out across the whole bridge pool.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeAgent {
+    pub runtime: String,
+    #[serde(flatten)]
+    pub agent: Agent,
+}
+
+/// The bridge's lifecycle as seen by the runtime pool. Replaces a bare
+/// `Option<AgentBridge>` so commands and the reconnect supervisor agree on whether a
+/// live bridge exists for a given runtime.
+enum ConnectionState {
+    Disconnected,
+    Connecting,
+    Connected(Arc<AgentBridge>),
+}
+
+impl ConnectionState {
+    fn bridge(&self) -> Option<&Arc<AgentBridge>> {
+        match self {
+            ConnectionState::Connected(bridge) => Some(bridge),
+            ConnectionState::Disconnected | ConnectionState::Connecting => None,
+        }
+    }
+}
+
+/// Payload for the `runtime-connection` event emitted to the webview.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ConnectionStatus {
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ConnectionEvent {
+    runtime: String,
+    status: ConnectionStatus,
+}
+
+fn emit_connection_status(app: &AppHandle, runtime: &str, status: ConnectionStatus) {
+    let _ = app.emit(
+        "runtime-connection",
+        ConnectionEvent {
+            runtime: runtime.to_string(),
+            status,
+        },
+    );
+}
+
+/// A named runtime connection: its current state plus the supervisor task keeping it
+/// alive, so `disconnect_runtime` can tear both down together.
+struct RuntimeHandle {
+    connection: Arc<Mutex<ConnectionState>>,
+    supervisor: JoinHandle<()>,
+}
+
+/// A keyed pool of runtime connections, so the desktop app can orchestrate agents
+/// spread across several agent runtimes instead of just one.
 pub struct AppState {
-    bridge: Arc<Mutex<Option<AgentBridge>>>,
+    runtimes: Arc<Mutex<HashMap<String, RuntimeHandle>>>,
 }
 
 impl Default for AppState {
     fn default() -> Self {
         Self {
-            bridge: Arc::new(Mutex::new(None)),
+            runtimes: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 }
 
+/// Looks up the live bridge for `runtime`, if any is currently connected.
+async fn bridge_for(state: &AppState, runtime: &str) -> Result<Arc<AgentBridge>, String> {
+    let runtimes = state.runtimes.lock().await;
+    let handle = runtimes
+        .get(runtime)
+        .ok_or_else(|| format!("Not connected to runtime '{}'", runtime))?;
+
+    handle
+        .connection
+        .lock()
+        .await
+        .bridge()
+        .cloned()
+        .ok_or_else(|| format!("Not connected to runtime '{}'", runtime))
+}
+
+/// Aborts a runtime's supervisor and shuts down its bridge (reader/heartbeat tasks and
+/// the socket itself), so replacing or dropping a `RuntimeHandle` never leaks either.
+async fn teardown_runtime(handle: RuntimeHandle) {
+    handle.supervisor.abort();
+
+    let bridge = handle.connection.lock().await.bridge().cloned();
+    if let Some(bridge) = bridge {
+        bridge.shutdown().await;
+    }
+}
+
 #[tauri::command]
-async fn connect_to_runtime(state: State<'_, AppState>) -> Result<bool, String> {
-    let mut bridge_guard = state.bridge.lock().await;
+async fn connect_to_runtime(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    name: String,
+    config: Option<RuntimeConnectionConfig>,
+) -> Result<bool, String> {
+    if let Some(existing) = state.runtimes.lock().await.remove(&name) {
+        teardown_runtime(existing).await;
+    }
+
+    let config = config.unwrap_or_default();
+    let connection = Arc::new(Mutex::new(ConnectionState::Connecting));
+    emit_connection_status(&app, &name, ConnectionStatus::Connecting);
 
-    match AgentBridge::connect("ws://127.0.0.1:9847").await {
+    match AgentBridge::connect(&config).await {
         Ok(bridge) => {
-            *bridge_guard = Some(bridge);
+            let bridge = Arc::new(bridge);
+            *connection.lock().await = ConnectionState::Connected(bridge.clone());
+            emit_connection_status(&app, &name, ConnectionStatus::Connected);
+
+            let supervisor = tokio::spawn(supervise_connection(
+                app,
+                name.clone(),
+                connection.clone(),
+                config,
+                bridge,
+            ));
+
+            state
+                .runtimes
+                .lock()
+                .await
+                .insert(name, RuntimeHandle { connection, supervisor });
+
             Ok(true)
         }
-        Err(e) => Err(format!("Failed to connect: {}", e))
+        Err(e) => {
+            emit_connection_status(&app, &name, ConnectionStatus::Disconnected);
+            Err(format!("Failed to connect to runtime '{}': {}", name, e))
+        }
+    }
+}
+
+#[tauri::command]
+async fn disconnect_runtime(state: State<'_, AppState>, name: String) -> Result<(), String> {
+    match state.runtimes.lock().await.remove(&name) {
+        Some(handle) => {
+            teardown_runtime(handle).await;
+            Ok(())
+        }
+        None => Err(format!("Not connected to runtime '{}'", name)),
+    }
+}
+
+/// Watches a connected bridge for death and keeps reconnecting (with the same
+/// connection config, under capped exponential backoff), emitting `runtime-connection`
+/// events for `name` as the state changes.
+async fn supervise_connection(
+    app: AppHandle,
+    name: String,
+    state: Arc<Mutex<ConnectionState>>,
+    config: RuntimeConnectionConfig,
+    mut bridge: Arc<AgentBridge>,
+) {
+    loop {
+        bridge.wait_until_dead().await;
+        *state.lock().await = ConnectionState::Disconnected;
+        emit_connection_status(&app, &name, ConnectionStatus::Disconnected);
+
+        let mut backoff = INITIAL_RECONNECT_BACKOFF;
+        let new_bridge = loop {
+            *state.lock().await = ConnectionState::Connecting;
+            emit_connection_status(&app, &name, ConnectionStatus::Connecting);
+
+            match AgentBridge::connect(&config).await {
+                Ok(bridge) => break Arc::new(bridge),
+                Err(_) => {
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+                }
+            }
+        };
+
+        // The old bridge is dead, but its reader/heartbeat tasks don't know that until
+        // shutdown aborts them, and a dropped JoinHandle does not abort its task.
+        bridge.shutdown().await;
+        bridge = new_bridge;
+
+        *state.lock().await = ConnectionState::Connected(bridge.clone());
+        emit_connection_status(&app, &name, ConnectionStatus::Connected);
     }
 }
 
 #[tauri::command]
 async fn start_agent(
     state: State<'_, AppState>,
+    runtime: String,
     config: AgentConfig,
 ) -> Result<Agent, String> {
-    let bridge_guard = state.bridge.lock().await;
-
-    if let Some(bridge) = bridge_guard.as_ref() {
-        bridge.start_agent(config).await
-    } else {
-        Err("Not connected to agent runtime".to_string())
-    }
+    bridge_for(&state, &runtime).await?.start_agent(config).await
 }
 
 #[tauri::command]
 async fn stop_agent(
     state: State<'_, AppState>,
+    runtime: String,
     agent_id: String,
 ) -> Result<(), String> {
-    let bridge_guard = state.bridge.lock().await;
+    bridge_for(&state, &runtime)
+        .await?
+        .stop_agent(&agent_id)
+        .await
+}
 
-    if let Some(bridge) = bridge_guard.as_ref() {
-        bridge.stop_agent(&agent_id).await
-    } else {
-        Err("Not connected to agent runtime".to_string())
-    }
+#[tauri::command]
+async fn list_agents(state: State<'_, AppState>, runtime: String) -> Result<Vec<Agent>, String> {
+    bridge_for(&state, &runtime).await?.list_agents().await
 }
 
+/// Fans `ListAgents` out across every connected runtime concurrently and merges the
+/// results, tagging each returned `Agent` with its originating runtime name. Runtimes
+/// that fail to answer are silently left out rather than failing the whole call.
 #[tauri::command]
-async fn list_agents(state: State<'_, AppState>) -> Result<Vec<Agent>, String> {
-    let bridge_guard = state.bridge.lock().await;
+async fn list_all_agents(state: State<'_, AppState>) -> Result<Vec<RuntimeAgent>, String> {
+    let bridges: Vec<(String, Arc<AgentBridge>)> = {
+        let runtimes = state.runtimes.lock().await;
+        let mut bridges = Vec::with_capacity(runtimes.len());
+        for (name, handle) in runtimes.iter() {
+            if let Some(bridge) = handle.connection.lock().await.bridge() {
+                bridges.push((name.clone(), bridge.clone()));
+            }
+        }
+        bridges
+    };
 
-    if let Some(bridge) = bridge_guard.as_ref() {
-        bridge.list_agents().await
-    } else {
-        Err("Not connected to agent runtime".to_string())
-    }
+    let per_runtime = join_all(bridges.into_iter().map(
+        |(name, bridge)| async move {
+            bridge.list_agents().await.map(|agents| {
+                agents
+                    .into_iter()
+                    .map(|agent| RuntimeAgent {
+                        runtime: name.clone(),
+                        agent,
+                    })
+                    .collect::<Vec<_>>()
+            })
+        },
+    ))
+    .await;
+
+    Ok(per_runtime.into_iter().filter_map(Result::ok).flatten().collect())
 }
 
 #[tauri::command]
 async fn get_agent(
     state: State<'_, AppState>,
+    runtime: String,
     agent_id: String,
 ) -> Result<Option<Agent>, String> {
-    let bridge_guard = state.bridge.lock().await;
-
-    if let Some(bridge) = bridge_guard.as_ref() {
-        bridge.get_agent(&agent_id).await
-    } else {
-        Err("Not connected to agent runtime".to_string())
-    }
+    bridge_for(&state, &runtime).await?.get_agent(&agent_id).await
 }
 
 #[tauri::command]
 async fn send_message(
     state: State<'_, AppState>,
+    runtime: String,
     agent_id: String,
     message: String,
 ) -> Result<(), String> {
-    let bridge_guard = state.bridge.lock().await;
+    bridge_for(&state, &runtime)
+        .await?
+        .send_message(&agent_id, &message)
+        .await
+}
 
-    if let Some(bridge) = bridge_guard.as_ref() {
-        bridge.send_message(&agent_id, &message).await
-    } else {
-        Err("Not connected to agent runtime".to_string())
-    }
+/// Subscribes the webview to an agent's live output on `runtime`. Forwards every
+/// `AgentOutput`/`AgentStatusChanged` frame for `agent_id` as an `agent-output` /
+/// `agent-status-changed` event. The subscription is tied to the bridge that's live at
+/// call time, so a reconnect (which replaces the bridge and its broadcast channel)
+/// closes the stream out from under it; when that happens an `agent-stream-ended` event
+/// is emitted instead of going silent, so the webview knows to call `subscribe_agent`
+/// again.
+#[tauri::command]
+async fn subscribe_agent(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    runtime: String,
+    agent_id: String,
+) -> Result<(), String> {
+    let mut rx = bridge_for(&state, &runtime).await?.subscribe();
+
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(RuntimeResponse::AgentOutput { agent_id: id, chunk }) if id == agent_id => {
+                    let _ = app.emit("agent-output", (&runtime, &id, &chunk));
+                }
+                Ok(RuntimeResponse::AgentStatusChanged { agent_id: id, status })
+                    if id == agent_id =>
+                {
+                    let _ = app.emit("agent-status-changed", (&runtime, &id, &status));
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => {
+                    let _ = app.emit("agent-stream-ended", (&runtime, &agent_id));
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(())
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -129,12 +405,81 @@ pub fn run() {
         .manage(AppState::default())
         .invoke_handler(tauri::generate_handler![
             connect_to_runtime,
+            disconnect_runtime,
             start_agent,
             stop_agent,
             list_agents,
+            list_all_agents,
             get_agent,
             send_message,
+            subscribe_agent,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod runtime_connection_config_tests {
+    use super::*;
+
+    #[test]
+    fn resolved_url_falls_back_to_the_hardcoded_default_when_unset() {
+        let config = RuntimeConnectionConfig::default();
+
+        assert_eq!(config.resolved_url(), DEFAULT_RUNTIME_URL);
+    }
+
+    #[test]
+    fn resolved_url_prefers_the_configured_value() {
+        let config = RuntimeConnectionConfig {
+            url: Some("ws://configured.test:1234".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(config.resolved_url(), "ws://configured.test:1234");
+    }
+
+    #[test]
+    fn resolved_auth_token_is_none_when_unset() {
+        let config = RuntimeConnectionConfig::default();
+
+        assert_eq!(config.resolved_auth_token(), None);
+    }
+
+    #[test]
+    fn resolved_connect_timeout_falls_back_to_the_hardcoded_default_when_unset() {
+        let config = RuntimeConnectionConfig::default();
+
+        assert_eq!(config.resolved_connect_timeout(), DEFAULT_CONNECT_TIMEOUT);
+    }
+
+    #[test]
+    fn resolved_connect_timeout_prefers_the_configured_value() {
+        let config = RuntimeConnectionConfig {
+            connect_timeout_ms: Some(5_000),
+            ..Default::default()
+        };
+
+        assert_eq!(config.resolved_connect_timeout(), Duration::from_millis(5_000));
+    }
+
+    #[test]
+    fn resolved_request_timeout_falls_back_to_the_agent_bridge_default_when_unset() {
+        let config = RuntimeConnectionConfig::default();
+
+        assert_eq!(
+            config.resolved_request_timeout(),
+            agent_bridge::DEFAULT_REQUEST_TIMEOUT
+        );
+    }
+
+    #[test]
+    fn resolved_request_timeout_prefers_the_configured_value() {
+        let config = RuntimeConnectionConfig {
+            request_timeout_ms: Some(2_500),
+            ..Default::default()
+        };
+
+        assert_eq!(config.resolved_request_timeout(), Duration::from_millis(2_500));
+    }
+}